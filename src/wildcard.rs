@@ -2,11 +2,12 @@ use std::collections::HashMap;
 
 pub(crate) type Result<T> = std::result::Result<T, WildcardError>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum WildcardError {
   InvalidConstraint(String),
   MissingName(String),
   RegexError(regex::Error),
+  Io(std::io::Error),
 }
 
 impl From<regex::Error> for WildcardError {
@@ -15,6 +16,12 @@ impl From<regex::Error> for WildcardError {
   }
 }
 
+impl From<std::io::Error> for WildcardError {
+  fn from(value: std::io::Error) -> Self {
+    Self::Io(value)
+  }
+}
+
 impl std::fmt::Display for WildcardError {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     match self {
@@ -24,10 +31,35 @@ impl std::fmt::Display for WildcardError {
       ),
       Self::MissingName(s) => write!(f, "The field '{s}' is not constrained by the match"),
       Self::RegexError(e) => write!(f, "{e}"),
+      Self::Io(e) => write!(f, "{e}"),
     }
   }
 }
 
+/// How narrowly a [`Wildcard`] pattern constrains a match, used to rank
+/// ambiguous rules against each other. Greater is more specific: fewer
+/// unconstrained `{name}` fields wins first, then more literal characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Specificity {
+  unconstrained: usize,
+  literal_chars: usize,
+}
+
+impl PartialOrd for Specificity {
+  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Specificity {
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    other
+      .unconstrained
+      .cmp(&self.unconstrained)
+      .then(self.literal_chars.cmp(&other.literal_chars))
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Wildcard {
   pub(crate) pattern: String,
@@ -89,6 +121,63 @@ impl Wildcard {
     }
     WildcardMap::new(map).ok()
   }
+
+  /// Rank how specifically this pattern constrains a match: a `{name,\d+}`
+  /// field beats a bare `{name}`, and more surrounding literal text beats
+  /// less.
+  pub(crate) fn specificity(&self) -> Specificity {
+    let wildcard_regex = wildcard_regex().expect("the wildcard regex is a fixed, valid pattern");
+    let mut unconstrained = 0;
+    let mut literal_chars = 0;
+    let mut last = 0;
+    for cap in wildcard_regex.captures_iter(&self.pattern) {
+      let full = cap.get(0).unwrap();
+      literal_chars += full.start() - last;
+      if cap.name("constraint").is_none() {
+        unconstrained += 1;
+      }
+      last = full.end();
+    }
+    literal_chars += self.pattern.len() - last;
+    Specificity { unconstrained, literal_chars }
+  }
+
+  /// Walk every file under `root`, reverse-matching it against this
+  /// pattern, and collect the set of concrete wildcard assignments
+  /// actually observed on disk. Mirrors Snakemake's `glob_wildcards`.
+  ///
+  /// `std::fs::read_dir` order is filesystem- and OS-dependent, so the
+  /// result is sorted before it's returned; otherwise an aggregate rule's
+  /// input order would vary across runs and machines for identical
+  /// directory contents.
+  pub fn glob_wildcards(&self, root: &str) -> Result<Vec<WildcardMap>> {
+    let mut maps = Vec::new();
+    for path in walk(root)? {
+      if let Some(map) = self.extract(&path) {
+        if !maps.contains(&map) {
+          maps.push(map);
+        }
+      }
+    }
+    maps.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    Ok(maps)
+  }
+}
+
+fn walk(root: &str) -> Result<Vec<String>> {
+  let mut files = Vec::new();
+  let mut dirs = vec![std::path::PathBuf::from(root)];
+  while let Some(dir) = dirs.pop() {
+    for entry in std::fs::read_dir(&dir)? {
+      let path = entry?.path();
+      if path.is_dir() {
+        dirs.push(path);
+      } else {
+        files.push(path.to_string_lossy().into_owned());
+      }
+    }
+  }
+  Ok(files)
 }
 
 #[derive(Debug, Clone)]
@@ -120,8 +209,32 @@ impl WildcardMap {
     result.push_str(&input[last..]);
     Ok(result)
   }
+
+  /// Apply `template` with each of `maps` in turn, e.g. to expand an
+  /// aggregate rule's inputs across every `{sample}` found by
+  /// [`Wildcard::glob_wildcards`].
+  pub fn expand(template: &str, maps: &[WildcardMap]) -> Result<Vec<String>> {
+    maps.iter().map(|map| map.apply(template)).collect()
+  }
+
+  /// A `HashMap`-iteration-order-independent key for sorting, so results
+  /// derived from it (e.g. [`Wildcard::glob_wildcards`]) are reproducible.
+  fn sort_key(&self) -> Vec<(&str, &str)> {
+    let mut entries: Vec<(&str, &str)> =
+      self.map.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    entries.sort();
+    entries
+  }
+}
+
+impl PartialEq for WildcardMap {
+  fn eq(&self, other: &Self) -> bool {
+    self.map == other.map
+  }
 }
 
+impl Eq for WildcardMap {}
+
 fn wildcard_regex() -> std::result::Result<regex::Regex, regex::Error> {
   regex::Regex::new(
     r"(?x)
@@ -156,4 +269,32 @@ mod tests {
     same_name: "path/to/{name}/{name}_{name}.txt", "path/to/output/output_output.txt",
     digits: "path/to/{name,\\d+}.txt", "path/to/0123.txt",
   );
+
+  #[test]
+  fn glob_wildcards_rejects_backref_mismatches_and_sorts_deterministically() {
+    let dir = std::env::temp_dir()
+      .join(format!("crabwalk-wildcard-test-{}-{}", std::process::id(), line!()));
+    std::fs::create_dir_all(dir.join("charlie")).unwrap();
+    std::fs::create_dir_all(dir.join("alpha")).unwrap();
+    std::fs::create_dir_all(dir.join("bravo")).unwrap();
+    std::fs::write(dir.join("charlie/charlie.txt"), "").unwrap();
+    std::fs::write(dir.join("alpha/alpha.txt"), "").unwrap();
+    std::fs::write(dir.join("bravo/bravo.txt"), "").unwrap();
+    // `{name}` repeats, so the directory and file name must agree; this one
+    // doesn't and should be rejected rather than matched.
+    std::fs::write(dir.join("bravo/mismatch.txt"), "").unwrap();
+
+    let pattern = format!("{}/{{name}}/{{name}}.txt", dir.display());
+    let wc = Wildcard::new(&pattern).unwrap();
+    let maps = wc.glob_wildcards(&dir.display().to_string()).unwrap();
+
+    let names: Vec<String> = maps.iter().map(|m| m.apply("{name}").unwrap()).collect();
+    assert_eq!(
+      names,
+      vec!["alpha".to_string(), "bravo".to_string(), "charlie".to_string()],
+      "mismatch.txt must be rejected by the backref check and the rest returned in sorted order"
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
 }
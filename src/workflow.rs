@@ -1,18 +1,116 @@
-use crate::wildcard::{Wildcard, WildcardMap};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Instant;
+
+use crate::wildcard::{Specificity, Wildcard, WildcardMap};
 
 pub struct Workflow {
   rules: Vec<Box<dyn Rule>>,
+  log: SharedLog,
 }
 
 pub trait Rule {
-  fn materialize(&self, path: &str) -> Option<Task>;
+  fn materialize(&self, path: &str, log: &SharedLog) -> Option<Task<'_>>;
 }
 
-type Result<T> = std::result::Result<T, Error>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+  Error,
+  Warn,
+  Info,
+  Debug,
+}
 
+impl std::fmt::Display for LogLevel {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let s = match self {
+      Self::Error => "ERROR",
+      Self::Warn => "WARN",
+      Self::Info => "INFO",
+      Self::Debug => "DEBUG",
+    };
+    write!(f, "{s}")
+  }
+}
+
+/// A single structured log record: a severity plus the rendered text.
 #[derive(Debug, Clone)]
+pub struct LogMessage {
+  pub level: LogLevel,
+  pub text: String,
+}
+
+impl LogMessage {
+  pub fn new(level: LogLevel, text: impl Into<String>) -> Self {
+    Self { level, text: text.into() }
+  }
+}
+
+/// A sink for [`LogMessage`]s. Implement `log` and the per-severity
+/// convenience methods come for free, modeled on pyruse's logging port.
+/// `Send` so a [`SharedLog`] can follow tasks onto worker threads.
+pub trait LogPort: Send {
+  fn log(&mut self, message: LogMessage);
+
+  fn error(&mut self, text: &str) {
+    self.log(LogMessage::new(LogLevel::Error, text));
+  }
+
+  fn warn(&mut self, text: &str) {
+    self.log(LogMessage::new(LogLevel::Warn, text));
+  }
+
+  fn info(&mut self, text: &str) {
+    self.log(LogMessage::new(LogLevel::Info, text));
+  }
+
+  fn debug(&mut self, text: &str) {
+    self.log(LogMessage::new(LogLevel::Debug, text));
+  }
+}
+
+/// Default [`LogPort`] that writes every message to stderr.
+#[derive(Debug, Default)]
+pub struct StderrLog;
+
+impl LogPort for StderrLog {
+  fn log(&mut self, message: LogMessage) {
+    eprintln!("[{}] {}", message.level, message.text);
+  }
+}
+
+pub type SharedLog = Arc<Mutex<dyn LogPort>>;
+
+fn default_log() -> SharedLog {
+  Arc::new(Mutex::new(StderrLog))
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
 pub enum Error {
   Wildcard(crate::wildcard::WildcardError),
+  Io(std::io::Error),
+  Yaml(serde_yaml::Error),
+  Json(serde_json::Error),
+  /// No rule produces this target and no file exists at the path either.
+  NoRuleForTarget(String),
+  /// A dependency cycle was found while resolving the DAG; the path lists
+  /// the targets visited from the first repeated node back to itself.
+  Cycle(Vec<String>),
+  /// A rule spawned from a [`WorkflowSpec`] command exited with a non-zero
+  /// status; the code is `None` if the process was killed by a signal.
+  CommandFailed(String, Option<i32>),
+  /// A task's closure panicked instead of returning an `Err`. Carries the
+  /// outputs it was producing and the panic payload, if it was a message,
+  /// so [`Workflow::build_parallel`] can report it like any other task
+  /// failure instead of leaving the scheduler waiting on a reply that will
+  /// never arrive.
+  TaskPanicked(Vec<String>, String),
+  /// More than one rule can produce this target, and neither `priority`
+  /// nor specificity broke the tie; the patterns are the tied candidates.
+  AmbiguousRule(String, Vec<String>),
 }
 
 impl From<crate::wildcard::WildcardError> for Error {
@@ -21,22 +119,99 @@ impl From<crate::wildcard::WildcardError> for Error {
   }
 }
 
+impl From<std::io::Error> for Error {
+  fn from(value: std::io::Error) -> Self {
+    Self::Io(value)
+  }
+}
+
+impl From<serde_yaml::Error> for Error {
+  fn from(value: serde_yaml::Error) -> Self {
+    Self::Yaml(value)
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(value: serde_json::Error) -> Self {
+    Self::Json(value)
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::Wildcard(e) => write!(f, "{e}"),
+      Self::Io(e) => write!(f, "{e}"),
+      Self::Yaml(e) => write!(f, "{e}"),
+      Self::Json(e) => write!(f, "{e}"),
+      Self::NoRuleForTarget(t) => {
+        write!(f, "no rule produces '{t}' and no such file exists")
+      }
+      Self::Cycle(path) => write!(f, "dependency cycle detected: {}", path.join(" -> ")),
+      Self::CommandFailed(command, code) => match code {
+        Some(code) => write!(f, "command `{command}` exited with status {code}"),
+        None => write!(f, "command `{command}` was terminated by a signal"),
+      },
+      Self::AmbiguousRule(target, patterns) => write!(
+        f,
+        "'{target}' can be produced by {} equally-ranked rules: {}",
+        patterns.len(),
+        patterns.join(", ")
+      ),
+      Self::TaskPanicked(outputs, message) => {
+        write!(f, "task for {outputs:?} panicked: {message}")
+      }
+    }
+  }
+}
+
+/// Extracts a human-readable message from a `catch_unwind` payload, which
+/// is almost always a `&'static str` or `String` (from `panic!`/`assert!`)
+/// but isn't guaranteed to be either.
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+  if let Some(s) = payload.downcast_ref::<&str>() {
+    s.to_string()
+  } else if let Some(s) = payload.downcast_ref::<String>() {
+    s.clone()
+  } else {
+    "non-string panic payload".to_string()
+  }
+}
+
 pub struct Task<'a> {
-  func: Box<dyn FnOnce() -> Result<()> + 'a>,
+  pub(crate) inputs: Vec<String>,
+  pub(crate) outputs: Vec<String>,
+  pub(crate) log: SharedLog,
+  /// The output pattern of the rule that produced this task, and the rank
+  /// it won resolution with, kept around so [`Workflow::resolve`] can
+  /// report [`Error::AmbiguousRule`] with something the user recognizes.
+  pub(crate) pattern: String,
+  pub(crate) priority: i32,
+  pub(crate) specificity: Specificity,
+  func: Box<dyn FnOnce() -> Result<()> + Send + 'a>,
+}
+
+/// DFS coloring used while resolving the dependency graph, so a node being
+/// re-entered while still on the stack is reported as a cycle rather than
+/// silently recursing forever.
+enum NodeState {
+  InProgress,
+  Done,
 }
 
 pub struct WildcardRule<F>
 where
-  F: Fn(&[String], &[String], &WildcardMap) -> Result<()>,
+  F: Fn(&[String], &[String], &WildcardMap) -> Result<()> + Sync,
 {
   inputs: Vec<String>,
   outputs: Vec<Wildcard>,
+  priority: i32,
   func: F,
 }
 
 impl<F> WildcardRule<F>
 where
-  F: Fn(&[String], &[String], &WildcardMap) -> Result<()>,
+  F: Fn(&[String], &[String], &WildcardMap) -> Result<()> + Sync,
 {
   pub fn new(inputs: &[String], outputs: &[String], func: F) -> Result<Self> {
     Ok(Self {
@@ -45,16 +220,25 @@ where
         .iter()
         .map(|s| Wildcard::new(s))
         .collect::<crate::wildcard::Result<Vec<_>>>()?,
+      priority: 0,
       func,
     })
   }
+
+  /// Rank this rule above or below others that can produce the same
+  /// target; ties are broken by [`Wildcard::specificity`] and, failing
+  /// that, rejected with [`Error::AmbiguousRule`]. Defaults to `0`.
+  pub fn with_priority(mut self, priority: i32) -> Self {
+    self.priority = priority;
+    self
+  }
 }
 
 impl<F> Rule for WildcardRule<F>
 where
-  F: Fn(&[String], &[String], &WildcardMap) -> Result<()>,
+  F: Fn(&[String], &[String], &WildcardMap) -> Result<()> + Sync,
 {
-  fn materialize(&self, path: &str) -> Option<Task> {
+  fn materialize(&self, path: &str, log: &SharedLog) -> Option<Task<'_>> {
     for output in self.outputs.iter() {
       if let Some(map) = output.extract(path) {
         let inputs = self
@@ -69,10 +253,19 @@ where
           .map(|o| map.apply(&o.pattern))
           .collect::<crate::wildcard::Result<Vec<_>>>()
           .ok()?;
+        log.lock().unwrap().debug(&format!(
+          "rule '{}' matched '{path}': inputs={inputs:?} outputs={outputs:?} wildcards={map:?}",
+          output.pattern,
+        ));
         return Some(Task {
+          inputs: inputs.clone(),
+          outputs: outputs.clone(),
+          log: log.clone(),
+          pattern: output.pattern.clone(),
+          priority: self.priority,
+          specificity: output.specificity(),
           func: Box::new(move || {
             (self.func)(&inputs, &outputs, &map)?;
-            println!("hi");
             Ok(())
           }),
         });
@@ -82,27 +275,659 @@ where
   }
 }
 
+/// A single rule in a declarative [`WorkflowSpec`]: wildcard `inputs` and
+/// `outputs` patterns plus a shell `command` template, with the resolved
+/// wildcards substituted in via [`WildcardMap::apply`] before it runs.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RuleSpec {
+  pub inputs: Vec<String>,
+  pub outputs: Vec<String>,
+  pub command: String,
+  /// Breaks ties when more than one rule can produce the same target; see
+  /// [`WildcardRule::with_priority`]. Defaults to `0`.
+  #[serde(default)]
+  pub priority: i32,
+}
+
+/// A config-driven description of a [`Workflow`], loadable with
+/// [`Workflow::from_yaml`] or [`Workflow::from_json`] so pipelines can be
+/// authored without recompiling.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct WorkflowSpec {
+  pub rules: Vec<RuleSpec>,
+}
+
+impl Workflow {
+  pub fn new(rules: Vec<Box<dyn Rule>>) -> Self {
+    Self { rules, log: default_log() }
+  }
+
+  pub fn with_logger(rules: Vec<Box<dyn Rule>>, log: SharedLog) -> Self {
+    Self { rules, log }
+  }
+
+  pub fn from_yaml(spec: &str) -> Result<Self> {
+    Self::from_spec(serde_yaml::from_str(spec)?)
+  }
+
+  pub fn from_json(spec: &str) -> Result<Self> {
+    Self::from_spec(serde_json::from_str(spec)?)
+  }
+
+  fn from_spec(spec: WorkflowSpec) -> Result<Self> {
+    let rules = spec
+      .rules
+      .into_iter()
+      .map(|rule| {
+        let command = rule.command;
+        let priority = rule.priority;
+        WildcardRule::new(&rule.inputs, &rule.outputs, move |_inputs, _outputs, map| {
+          let resolved = map.apply(&command)?;
+          let status = std::process::Command::new("sh").arg("-c").arg(&resolved).status()?;
+          if !status.success() {
+            return Err(Error::CommandFailed(resolved, status.code()));
+          }
+          Ok(())
+        })
+        .map(|rule| Box::new(rule.with_priority(priority)) as Box<dyn Rule>)
+      })
+      .collect::<Result<Vec<_>>>()?;
+    Ok(Self::new(rules))
+  }
+
+  /// Resolve `target` against the registered rules, recursing into its
+  /// inputs Make/Snakemake-style, then run every out-of-date task in
+  /// dependency order.
+  ///
+  /// A target with no matching rule is treated as a leaf: it must already
+  /// exist on disk, or resolution fails with [`Error::NoRuleForTarget`].
+  pub fn build(&self, target: &str) -> Result<()> {
+    let mut tasks = HashMap::new();
+    let mut state = HashMap::new();
+    let mut order = Vec::new();
+    self.resolve(target, &mut tasks, &mut state, &mut order, &mut Vec::new())?;
+
+    for target in order {
+      if let Some(task) = tasks.remove(&target) {
+        if !Self::is_up_to_date(&task.inputs, &task.outputs)? {
+          Self::run(task)?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Like [`Workflow::build`], but independent branches of the dependency
+  /// graph run concurrently on a worker pool capped at `max_jobs`. A node
+  /// becomes runnable once every task it depends on has finished; the
+  /// first error encountered is returned once all in-flight tasks drain,
+  /// and no further tasks are dispatched after that.
+  pub fn build_parallel(&self, target: &str, max_jobs: usize) -> Result<()> {
+    let max_jobs = max_jobs.max(1);
+
+    let mut tasks = HashMap::new();
+    let mut state = HashMap::new();
+    let mut order = Vec::new();
+    self.resolve(target, &mut tasks, &mut state, &mut order, &mut Vec::new())?;
+
+    // Remaining dependency count per task, and the reverse edges (a
+    // dependency -> the tasks it unblocks once finished). Inputs with no
+    // task entry are leaf sources and don't gate anything.
+    let mut remaining: HashMap<String, usize> = HashMap::new();
+    let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+    for (target, task) in tasks.iter() {
+      let deps: Vec<&String> = task.inputs.iter().filter(|i| tasks.contains_key(*i)).collect();
+      remaining.insert(target.clone(), deps.len());
+      for dep in deps {
+        dependents.entry(dep.clone()).or_default().push(target.clone());
+      }
+    }
+
+    let mut ready: Vec<String> =
+      remaining.iter().filter(|(_, &count)| count == 0).map(|(t, _)| t.clone()).collect();
+
+    std::thread::scope(|scope| {
+      let (done_tx, done_rx) = mpsc::channel::<(String, Result<()>)>();
+      let mut in_flight = 0usize;
+      let mut error = None;
+
+      loop {
+        while error.is_none() && in_flight < max_jobs {
+          let Some(next) = ready.pop() else { break };
+          let task = tasks.remove(&next).unwrap();
+          match Self::is_up_to_date(&task.inputs, &task.outputs) {
+            Ok(true) => Self::unblock(&next, &mut dependents, &mut remaining, &mut ready),
+            Ok(false) => {
+              in_flight += 1;
+              let done_tx = done_tx.clone();
+              let outputs = task.outputs.clone();
+              scope.spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| Self::run(task)))
+                  .unwrap_or_else(|payload| Err(Error::TaskPanicked(outputs, panic_message(payload))));
+                let _ = done_tx.send((next, result));
+              });
+            }
+            Err(e) => error = Some(e),
+          }
+        }
+
+        if in_flight == 0 {
+          break;
+        }
+
+        let (finished, result) = done_rx.recv().unwrap();
+        in_flight -= 1;
+        match result {
+          Ok(()) => Self::unblock(&finished, &mut dependents, &mut remaining, &mut ready),
+          Err(e) => {
+            error.get_or_insert(e);
+          }
+        }
+      }
+
+      match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+      }
+    })
+  }
+
+  /// Decrement the remaining-dependency count of everything `finished`
+  /// unblocks, queuing any that reach zero.
+  fn unblock(
+    finished: &str,
+    dependents: &mut HashMap<String, Vec<String>>,
+    remaining: &mut HashMap<String, usize>,
+    ready: &mut Vec<String>,
+  ) {
+    for dependent in dependents.remove(finished).unwrap_or_default() {
+      let count = remaining.get_mut(&dependent).unwrap();
+      *count -= 1;
+      if *count == 0 {
+        ready.push(dependent);
+      }
+    }
+  }
+
+  fn run(task: Task<'_>) -> Result<()> {
+    let log = task.log.clone();
+    log.lock().unwrap().info(&format!("running task for {:?}", task.outputs));
+    let start = Instant::now();
+    let result = (task.func)();
+    let elapsed = start.elapsed();
+    match &result {
+      Ok(()) => {
+        log.lock().unwrap().info(&format!("finished task for {:?} in {elapsed:?}", task.outputs));
+      }
+      Err(e) => {
+        log.lock().unwrap().error(&format!(
+          "task for {:?} failed after {elapsed:?}: {e}",
+          task.outputs,
+        ));
+      }
+    }
+    result
+  }
+
+  /// Resolves `target` to at most one task. When several rules can
+  /// produce it, the one with the highest `priority` wins; ties fall back
+  /// to [`Wildcard::specificity`]; a tie there too is
+  /// [`Error::AmbiguousRule`].
+  fn resolve<'a>(
+    &'a self,
+    target: &str,
+    tasks: &mut HashMap<String, Task<'a>>,
+    state: &mut HashMap<String, NodeState>,
+    order: &mut Vec<String>,
+    stack: &mut Vec<String>,
+  ) -> Result<()> {
+    match state.get(target) {
+      Some(NodeState::Done) => return Ok(()),
+      Some(NodeState::InProgress) => {
+        let start = stack.iter().position(|t| t == target).unwrap();
+        let mut cycle = stack[start..].to_vec();
+        cycle.push(target.to_string());
+        return Err(Error::Cycle(cycle));
+      }
+      None => {}
+    }
+
+    state.insert(target.to_string(), NodeState::InProgress);
+    stack.push(target.to_string());
+
+    let mut candidates: Vec<Task> =
+      self.rules.iter().filter_map(|rule| rule.materialize(target, &self.log)).collect();
+
+    match candidates.len() {
+      0 if Path::new(target).exists() => {}
+      0 => return Err(Error::NoRuleForTarget(target.to_string())),
+      _ => {
+        // Highest priority first, then most specific; a still-tied pair of
+        // leaders at the front is an unresolved ambiguity.
+        candidates.sort_by(|a, b| b.priority.cmp(&a.priority).then(b.specificity.cmp(&a.specificity)));
+        let winner = &candidates[0];
+        let tied: Vec<&str> = candidates
+          .iter()
+          .take_while(|t| t.priority == winner.priority && t.specificity == winner.specificity)
+          .map(|t| t.pattern.as_str())
+          .collect();
+        if tied.len() > 1 {
+          return Err(Error::AmbiguousRule(
+            target.to_string(),
+            tied.into_iter().map(str::to_string).collect(),
+          ));
+        }
+
+        let task = candidates.remove(0);
+        for input in task.inputs.clone() {
+          self.resolve(&input, tasks, state, order, stack)?;
+        }
+        tasks.insert(target.to_string(), task);
+      }
+    }
+
+    stack.pop();
+    state.insert(target.to_string(), NodeState::Done);
+    order.push(target.to_string());
+    Ok(())
+  }
+
+  /// Reverse-match `pattern` against every file under `root`, returning
+  /// the concrete wildcard assignments found on disk. Useful for
+  /// aggregate rules whose inputs are "every `{sample}` that exists" —
+  /// see [`Wildcard::glob_wildcards`] and the companion
+  /// [`WildcardMap::expand`].
+  pub fn glob_wildcards(pattern: &str, root: &str) -> Result<Vec<WildcardMap>> {
+    Ok(Wildcard::new(pattern)?.glob_wildcards(root)?)
+  }
+
+  /// A task is up to date (and can be skipped) when every output already
+  /// exists and is newer than every input.
+  fn is_up_to_date(inputs: &[String], outputs: &[String]) -> Result<bool> {
+    for output in outputs {
+      let output_mtime = match std::fs::metadata(output).and_then(|m| m.modified()) {
+        Ok(mtime) => mtime,
+        Err(_) => return Ok(false),
+      };
+      for input in inputs {
+        if std::fs::metadata(input)?.modified()? >= output_mtime {
+          return Ok(false);
+        }
+      }
+    }
+    Ok(true)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  /// A fresh, unique directory under the OS temp dir, so tests that build
+  /// bare target names (e.g. "a", "final") don't collide with each other,
+  /// with cargo's own `./target`, or with anything else sitting at the
+  /// repo root.
+  fn unique_temp_dir(label: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let dir =
+      std::env::temp_dir().join(format!("crabwalk-{label}-test-{}-{n}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn build_detects_cycles() {
+    let a = WildcardRule::new(&["b".to_string()], &["a".to_string()], |_, _, _| Ok(())).unwrap();
+    let b = WildcardRule::new(&["a".to_string()], &["b".to_string()], |_, _, _| Ok(())).unwrap();
+    let workflow = Workflow::new(vec![Box::new(a), Box::new(b)]);
+
+    match workflow.build("a") {
+      Err(Error::Cycle(path)) => {
+        assert_eq!(path, vec!["a".to_string(), "b".to_string(), "a".to_string()])
+      }
+      other => panic!("expected Error::Cycle, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn build_detects_cycles_not_rooted_at_the_build_target() {
+    // a -> b -> c -> b: the cycle is b <-> c, and the reported path should
+    // not drag in the unrelated ancestor "a" on its way down from the
+    // build target.
+    let a = WildcardRule::new(&["b".to_string()], &["a".to_string()], |_, _, _| Ok(())).unwrap();
+    let b = WildcardRule::new(&["c".to_string()], &["b".to_string()], |_, _, _| Ok(())).unwrap();
+    let c = WildcardRule::new(&["b".to_string()], &["c".to_string()], |_, _, _| Ok(())).unwrap();
+    let workflow = Workflow::new(vec![Box::new(a), Box::new(b), Box::new(c)]);
+
+    match workflow.build("a") {
+      Err(Error::Cycle(path)) => {
+        assert_eq!(path, vec!["b".to_string(), "c".to_string(), "b".to_string()])
+      }
+      other => panic!("expected Error::Cycle, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn build_fails_for_missing_leaf() {
+    let rule = WildcardRule::new(
+      &["no/such/file".to_string()],
+      &["target".to_string()],
+      |_, _, _| Ok(()),
+    )
+    .unwrap();
+    let workflow = Workflow::new(vec![Box::new(rule)]);
+
+    match workflow.build("target") {
+      Err(Error::NoRuleForTarget(target)) => assert_eq!(target, "no/such/file"),
+      other => panic!("expected Error::NoRuleForTarget, got {other:?}"),
+    }
+  }
+
   #[test]
-  fn workflow() {
+  fn build_skips_up_to_date_outputs() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join(format!("crabwalk-build-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("in.txt");
+    let output = dir.join("out.txt");
+    std::fs::write(&input, "hi").unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    std::fs::write(&output, "hi").unwrap();
+
+    let ran = Arc::new(AtomicBool::new(false));
+    let ran_clone = ran.clone();
     let rule = WildcardRule::new(
-      &["path/to/{file}/{blah}.in".to_string()],
-      &["path/to/{file}.out".to_string()],
-      |i, o, m| {
-        println!("inputs: {i:?}");
-        println!("outputs: {o:?}");
-        println!("wildcards: {m:?}");
+      &[input.to_string_lossy().into_owned()],
+      &[output.to_string_lossy().into_owned()],
+      move |_, _, _| {
+        ran_clone.store(true, Ordering::SeqCst);
         Ok(())
       },
-    ).unwrap();
-    // let workflow = Workflow {
-    //   rules: vec![Box::new(rule)],
-    // };
-    let task = rule.materialize("path/to/filename.out").unwrap();
-    (task.func)().unwrap();
-    assert!(false);
+    )
+    .unwrap();
+    let workflow = Workflow::new(vec![Box::new(rule)]);
+
+    workflow.build(&output.to_string_lossy()).unwrap();
+    assert!(!ran.load(Ordering::SeqCst), "an up-to-date output should not be rebuilt");
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn aggregate_rule_builds_from_glob_discovered_inputs() {
+    let dir =
+      std::env::temp_dir().join(format!("crabwalk-aggregate-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for sample in ["alpha", "bravo", "charlie"] {
+      std::fs::write(dir.join(format!("{sample}.in")), sample).unwrap();
+    }
+
+    // Mirrors the aggregate-rule recipe from Workflow::glob_wildcards's
+    // docs: reverse-match the filesystem for every existing `{sample}`,
+    // then expand the pattern into a concrete input list for one rule.
+    let sample_pattern = format!("{}/{{sample}}.in", dir.display());
+    let maps = Workflow::glob_wildcards(&sample_pattern, &dir.display().to_string()).unwrap();
+    let inputs = WildcardMap::expand(&sample_pattern, &maps).unwrap();
+
+    let seen: Arc<Mutex<Vec<String>>> = Arc::default();
+    let seen_clone = seen.clone();
+    let output = dir.join("all.out");
+    let rule = WildcardRule::new(&inputs, &[output.to_string_lossy().into_owned()], move |i, _, _| {
+      seen_clone.lock().unwrap().extend_from_slice(i);
+      Ok(())
+    })
+    .unwrap();
+    let workflow = Workflow::new(vec![Box::new(rule)]);
+
+    workflow.build(&output.to_string_lossy()).unwrap();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 3, "all three discovered samples should feed the aggregate task");
+    for sample in ["alpha", "bravo", "charlie"] {
+      assert!(
+        seen.iter().any(|i| i.ends_with(&format!("{sample}.in"))),
+        "missing {sample}.in among aggregate inputs: {seen:?}"
+      );
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn from_yaml_runs_the_spec_s_command() {
+    let dir = std::env::temp_dir().join(format!("crabwalk-yaml-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("out.txt");
+
+    let spec = format!(
+      "rules:\n  - inputs: []\n    outputs: [\"{out}\"]\n    command: \"echo hi > {out}\"\n",
+      out = output.display()
+    );
+    let workflow = Workflow::from_yaml(&spec).unwrap();
+    workflow.build(&output.to_string_lossy()).unwrap();
+    assert!(output.exists());
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn from_json_reports_command_failed() {
+    let spec = r#"{"rules":[{"inputs":[],"outputs":["chunk0-2-nonexistent-target"],"command":"exit 7"}]}"#;
+    let workflow = Workflow::from_json(spec).unwrap();
+
+    match workflow.build("chunk0-2-nonexistent-target") {
+      Err(Error::CommandFailed(command, Some(7))) => assert_eq!(command, "exit 7"),
+      other => panic!("expected Error::CommandFailed, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn resolve_prefers_higher_priority_rule() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // An isolated temp dir, not a bare repo-root-relative name: a stray
+    // file there (cargo's own ./target included) would make
+    // is_up_to_date consider the task already satisfied and skip running
+    // either closure, passing vacuously regardless of which rule won.
+    let dir = unique_temp_dir("priority");
+    let target = dir.join("target").to_string_lossy().into_owned();
+
+    let low_ran = Arc::new(AtomicBool::new(false));
+    let high_ran = Arc::new(AtomicBool::new(false));
+    let low_ran_clone = low_ran.clone();
+    let high_ran_clone = high_ran.clone();
+
+    let low = WildcardRule::new(&[], std::slice::from_ref(&target), move |_, _, _| {
+      low_ran_clone.store(true, Ordering::SeqCst);
+      Ok(())
+    })
+    .unwrap();
+    let high = WildcardRule::new(&[], std::slice::from_ref(&target), move |_, _, _| {
+      high_ran_clone.store(true, Ordering::SeqCst);
+      Ok(())
+    })
+    .unwrap()
+    .with_priority(1);
+    let workflow = Workflow::new(vec![Box::new(low), Box::new(high)]);
+
+    // Both rules can produce the target; priority alone should break the
+    // tie without hitting Error::AmbiguousRule.
+    workflow.build(&target).unwrap();
+
+    assert!(high_ran.load(Ordering::SeqCst), "higher-priority rule should have run");
+    assert!(!low_ran.load(Ordering::SeqCst), "lower-priority rule should not have run");
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn resolve_prefers_more_specific_rule_when_priorities_tie() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // See the comment in resolve_prefers_higher_priority_rule above.
+    let dir = unique_temp_dir("specificity");
+    let target = dir.join("target").to_string_lossy().into_owned();
+
+    let generic_ran = Arc::new(AtomicBool::new(false));
+    let specific_ran = Arc::new(AtomicBool::new(false));
+    let generic_ran_clone = generic_ran.clone();
+    let specific_ran_clone = specific_ran.clone();
+
+    let generic_pattern = format!("{}/{{name}}", dir.display());
+    let generic = WildcardRule::new(&[], &[generic_pattern], move |_, _, _| {
+      generic_ran_clone.store(true, Ordering::SeqCst);
+      Ok(())
+    })
+    .unwrap();
+    let specific = WildcardRule::new(&[], std::slice::from_ref(&target), move |_, _, _| {
+      specific_ran_clone.store(true, Ordering::SeqCst);
+      Ok(())
+    })
+    .unwrap();
+    let workflow = Workflow::new(vec![Box::new(generic), Box::new(specific)]);
+
+    workflow.build(&target).unwrap();
+
+    assert!(specific_ran.load(Ordering::SeqCst), "more-specific rule should have run");
+    assert!(!generic_ran.load(Ordering::SeqCst), "less-specific rule should not have run");
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn resolve_reports_ambiguous_rule_when_unbroken() {
+    let dir = unique_temp_dir("ambiguous");
+    let target = dir.join("target").to_string_lossy().into_owned();
+
+    let a = WildcardRule::new(&[], std::slice::from_ref(&target), |_, _, _| Ok(())).unwrap();
+    let b = WildcardRule::new(&[], std::slice::from_ref(&target), |_, _, _| Ok(())).unwrap();
+    let workflow = Workflow::new(vec![Box::new(a), Box::new(b)]);
+
+    match workflow.build(&target) {
+      Err(Error::AmbiguousRule(got_target, patterns)) => {
+        assert_eq!(got_target, target);
+        assert_eq!(patterns, vec![target.clone(), target.clone()]);
+      }
+      other => panic!("expected Error::AmbiguousRule, got {other:?}"),
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[derive(Default)]
+  struct RecordingLog(Arc<Mutex<Vec<LogMessage>>>);
+
+  impl LogPort for RecordingLog {
+    fn log(&mut self, message: LogMessage) {
+      self.0.lock().unwrap().push(message);
+    }
+  }
+
+  #[test]
+  fn run_logs_failures_at_error_level() {
+    let messages: Arc<Mutex<Vec<LogMessage>>> = Arc::default();
+    let log: SharedLog = Arc::new(Mutex::new(RecordingLog(messages.clone())));
+
+    let rule = WildcardRule::new(&[], &["fails".to_string()], |_, _, _| {
+      Err(Error::CommandFailed("boom".to_string(), Some(1)))
+    })
+    .unwrap();
+    let workflow = Workflow::with_logger(vec![Box::new(rule)], log);
+
+    assert!(workflow.build("fails").is_err());
+
+    let messages = messages.lock().unwrap();
+    assert!(
+      messages
+        .iter()
+        .any(|m| m.level == LogLevel::Error && m.text.contains("boom")),
+      "expected an Error-level record mentioning the failure, got {messages:?}"
+    );
+    assert!(
+      !messages
+        .iter()
+        .any(|m| m.level == LogLevel::Info && m.text.starts_with("finished task")),
+      "a failed task should not also log the success-path \"finished\" message"
+    );
+  }
+
+  #[test]
+  fn build_parallel_converts_panics_to_errors() {
+    let dir = unique_temp_dir("panics");
+    let target = dir.join("panics").to_string_lossy().into_owned();
+
+    let rule = WildcardRule::new(&[], std::slice::from_ref(&target), |_, _, _| panic!("boom")).unwrap();
+    let workflow = Workflow::new(vec![Box::new(rule)]);
+    match workflow.build_parallel(&target, 2) {
+      Err(Error::TaskPanicked(outputs, message)) => {
+        assert_eq!(outputs, vec![target.clone()]);
+        assert!(message.contains("boom"), "unexpected panic message: {message}");
+      }
+      other => panic!("expected Error::TaskPanicked, got {other:?}"),
+    }
+
+    std::fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn build_parallel_stops_dispatching_after_an_error() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    let dir = unique_temp_dir("stop-dispatch");
+    let a = dir.join("a").to_string_lossy().into_owned();
+    let b = dir.join("b").to_string_lossy().into_owned();
+    let independent_target = dir.join("independent").to_string_lossy().into_owned();
+    let final_target = dir.join("final").to_string_lossy().into_owned();
+
+    let independent_ran = Arc::new(AtomicBool::new(false));
+    let independent_ran_clone = independent_ran.clone();
+
+    let failing = WildcardRule::new(&[], std::slice::from_ref(&a), |_, _, _| {
+      Err(Error::CommandFailed("boom".to_string(), Some(1)))
+    })
+    .unwrap();
+    let slow = WildcardRule::new(&[], std::slice::from_ref(&b), |_, _, _| {
+      std::thread::sleep(Duration::from_millis(50));
+      Ok(())
+    })
+    .unwrap();
+    // Only becomes ready once `slow` finishes, which happens after `a`
+    // has already failed and set the scheduler's error.
+    let independent = WildcardRule::new(
+      std::slice::from_ref(&b),
+      std::slice::from_ref(&independent_target),
+      move |_, _, _| {
+        independent_ran_clone.store(true, Ordering::SeqCst);
+        Ok(())
+      },
+    )
+    .unwrap();
+    let combined = WildcardRule::new(
+      &[a.clone(), independent_target.clone()],
+      std::slice::from_ref(&final_target),
+      |_, _, _| Ok(()),
+    )
+    .unwrap();
+
+    let workflow = Workflow::new(vec![
+      Box::new(failing),
+      Box::new(slow),
+      Box::new(independent),
+      Box::new(combined),
+    ]);
+    let result = workflow.build_parallel(&final_target, 2);
+
+    assert!(result.is_err());
+    assert!(
+      !independent_ran.load(Ordering::SeqCst),
+      "no task should be dispatched once an earlier task has failed"
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
   }
 }